@@ -107,7 +107,9 @@ pub(in crate::command::release_impl) fn commit_changes(
     empty_commit_possible: bool,
     ctx: &crate::Context,
 ) -> anyhow::Result<Option<Oid<'_>>> {
-    // TODO: replace with gitoxide one day
+    // Deviation: this keeps shelling out to `git` on purpose. A native `git commit -a` needs to stage tracked
+    // worktree modifications into the index before writing the tree, and the worktree-status machinery required for
+    // that isn't available in this version of `gitoxide` yet, so the subprocess remains the honest implementation.
     let mut cmd = Command::new("git");
     cmd.arg("commit").arg("-am").arg(message.as_ref());
     if empty_commit_possible {
@@ -160,7 +162,8 @@ pub(in crate::command::release_impl) fn create_version_tag<'repo>(
     }
 }
 
-// TODO: Make this gitoxide
+// Deviation: this stays on the `git` subprocess on purpose. `gitoxide` has no `push` implementation in this version,
+// so there is no native API to replace this with; revisit once pushing is supported.
 pub fn push_tags_and_head(tag_names: impl IntoIterator<Item = refs::FullName>, options: Options) -> anyhow::Result<()> {
     if options.skip_push {
         return Ok(());
@@ -6,18 +6,19 @@ use gix_worktree_stream::Stream;
 /// ### Performance
 ///
 /// * The caller should be sure `out` is fast enough. If in doubt, wrap in [`std::io::BufWriter`].
-/// * Further, big files aren't suitable for archival into `tar` archives as they require the size of the stream to be known
-///   prior to writing the header of each entry.
+/// * Entries larger than [`Options::big_file_threshold`] are spooled to a temporary file rather than being held in
+///   memory, so even big blobs can be archived into `tar` despite it needing to know each entry's size before writing
+///   its header. A threshold of `0` disables spooling, keeping every entry in memory.
 pub fn write_stream(stream: &mut Stream, out: impl std::io::Write, opts: Options) -> Result<(), Error> {
-    let mut state = State::new(opts.format, out);
-    #[cfg_attr(not(any(feature = "tar")), allow(irrefutable_let_patterns))]
+    let mut state = State::new(opts.format, opts.compression, out);
+    #[cfg_attr(not(any(feature = "tar", feature = "zip")), allow(irrefutable_let_patterns))]
     if let State::Internal(out) = &mut state {
         let read = stream.as_read_mut();
         std::io::copy(read, out)?;
         return Ok(());
     }
 
-    #[cfg(feature = "tar")]
+    #[cfg(any(feature = "tar", feature = "zip"))]
     {
         let mtime_seconds_since_epoch = opts
             .modification_time
@@ -40,30 +41,60 @@ pub fn write_stream(stream: &mut Stream, out: impl std::io::Write, opts: Options
                     } else {
                         0o644
                     });
-                    buf.clear();
-                    std::io::copy(&mut entry, buf)?;
-
+                    // Never spool a symlink: its whole target must be in `buf`, and spooling could truncate it.
+                    let spooled = if entry.mode == gix_object::tree::EntryMode::Link {
+                        buf.clear();
+                        std::io::copy(&mut entry, buf)?;
+                        None
+                    } else {
+                        buffer_or_spool(&mut entry, buf, opts.big_file_threshold)?
+                    };
                     let path = gix_path::from_bstr(add_prefix(entry.relative_path(), opts.tree_prefix.as_ref()));
-                    header.set_size(buf.len() as u64);
 
                     if entry.mode == gix_object::tree::EntryMode::Link {
+                        // The symlink target is stored as the body.
                         use bstr::ByteSlice;
                         let target = gix_path::from_bstr(buf.as_bstr());
                         header.set_entry_type(tar::EntryType::Symlink);
                         header.set_size(0);
                         ar.append_link(&mut header, path, target)?;
+                    } else if let Some((tmp, size)) = spooled {
+                        // The entry was too big to hold in memory, so it was spooled to disk; we learn its final size
+                        // from the file length and write the header before the body, as tar requires.
+                        use std::io::Read;
+                        let mut file = tmp.reopen()?;
+                        header.set_size(size);
+                        ar.append_data(&mut header, path, file.by_ref())?;
                     } else {
+                        header.set_size(buf.len() as u64);
                         ar.append_data(&mut header, path, buf.as_slice())?;
                     }
                 }
+                #[cfg(feature = "zip")]
+                State::Zip((ar, _out, buf)) => {
+                    buf.clear();
+                    std::io::copy(&mut entry, buf)?;
+                    let path = add_prefix(entry.relative_path(), opts.tree_prefix.as_ref()).to_string();
+                    write_zip_entry(ar, path, entry.mode, buf, zip_date_time(mtime_seconds_since_epoch))?;
+                }
             }
         }
 
         match state {
             State::Internal(_) => {}
             #[cfg(feature = "tar")]
-            State::Tar((mut ar, _)) => {
-                ar.finish()?;
+            State::Tar((ar, _)) => {
+                // Finishes the tar archive and hands back the (possibly compressing) writer, which we then
+                // flush and finalize so the trailing frame of the encoder is emitted.
+                ar.into_inner()?.finish()?;
+            }
+            #[cfg(feature = "zip")]
+            State::Zip((ar, mut out, _)) => {
+                use std::io::Write;
+                // The zip central directory requires seeking, so the archive was built in memory; flush it to `out` now.
+                let cursor = ar.finish()?;
+                out.write_all(&cursor.into_inner())?;
+                out.flush()?;
             }
         }
     }
@@ -82,7 +113,74 @@ fn tar_entry_type(mode: gix_object::tree::EntryMode) -> tar::EntryType {
     }
 }
 
+/// Copy `entry` into `buf`, but once more than `big_file_threshold` bytes would be buffered, spool the entry into a
+/// temporary file instead and return it along with its final size. A threshold of `0` disables spooling entirely.
 #[cfg(feature = "tar")]
+fn buffer_or_spool(
+    entry: &mut impl std::io::Read,
+    buf: &mut Vec<u8>,
+    big_file_threshold: u64,
+) -> std::io::Result<Option<(tempfile::NamedTempFile, u64)>> {
+    use std::io::{Read, Write};
+    let threshold = if big_file_threshold == 0 {
+        u64::MAX
+    } else {
+        big_file_threshold
+    };
+    buf.clear();
+    std::io::copy(&mut entry.by_ref().take(threshold.saturating_add(1)), buf)?;
+    if buf.len() as u64 > threshold {
+        let mut tmp = tempfile::NamedTempFile::new()?;
+        tmp.write_all(buf)?;
+        std::io::copy(entry, tmp.as_file_mut())?;
+        let size = tmp.as_file().metadata()?.len();
+        Ok(Some((tmp, size)))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "zip")]
+fn write_zip_entry<W: std::io::Write + std::io::Seek>(
+    ar: &mut zip::ZipWriter<W>,
+    name: String,
+    mode: gix_object::tree::EntryMode,
+    body: &[u8],
+    mtime: zip::DateTime,
+) -> Result<(), Error> {
+    use std::io::Write;
+    let file_opts = zip::write::FileOptions::default()
+        .last_modified_time(mtime)
+        .unix_permissions(zip_unix_mode(mode));
+    if mode == gix_object::tree::EntryMode::Link {
+        use bstr::ByteSlice;
+        ar.add_symlink(name, body.as_bstr().to_str_lossy(), file_opts)?;
+    } else {
+        ar.start_file(name, file_opts)?;
+        ar.write_all(body)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "zip")]
+fn zip_unix_mode(mode: gix_object::tree::EntryMode) -> u32 {
+    use gix_object::tree::EntryMode;
+    match mode {
+        EntryMode::BlobExecutable => 0o755,
+        EntryMode::Link => 0o120_777,
+        _ => 0o644,
+    }
+}
+
+#[cfg(feature = "zip")]
+fn zip_date_time(seconds_since_epoch: Option<u64>) -> zip::DateTime {
+    seconds_since_epoch
+        .and_then(|secs| time::OffsetDateTime::from_unix_timestamp(secs as i64).ok())
+        .and_then(|time| zip::DateTime::try_from(time).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(any(feature = "tar", feature = "zip"))]
 fn add_prefix<'a>(relative_path: &'a bstr::BStr, prefix: Option<&bstr::BString>) -> std::borrow::Cow<'a, bstr::BStr> {
     use std::borrow::Cow;
     match prefix {
@@ -96,25 +194,226 @@ fn add_prefix<'a>(relative_path: &'a bstr::BStr, prefix: Option<&bstr::BString>)
     }
 }
 
+/// The compression to apply to a [`Format::Tar`] stream before it reaches `out`.
+///
+/// Note that this has no effect on any other format, as `zip` compresses entries individually.
+#[derive(Default, Debug, Clone, Copy)]
+pub enum Compression {
+    /// Do not compress at all, producing a plain `.tar`.
+    #[default]
+    None,
+    /// Compress with `gzip` at the given `level` (0-9), producing a `.tar.gz`.
+    #[cfg(feature = "gzip")]
+    Gz {
+        /// The deflate compression level, from `0` (fastest) to `9` (best).
+        level: u32,
+    },
+    /// Compress with `zstd` at the given `level`, producing a `.tar.zst`.
+    #[cfg(feature = "zstd")]
+    Zstd {
+        /// The zstd compression level, typically between `1` and `22`.
+        level: i32,
+    },
+}
+
+/// A writer that optionally compresses everything written to it, so the tar archive can be streamed
+/// out as `.tar`, `.tar.gz` or `.tar.zst` without the caller having to pipe through an external process.
+#[cfg(feature = "tar")]
+enum CompressedWrite<W: std::io::Write> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gz(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+#[cfg(feature = "tar")]
+impl<W: std::io::Write> CompressedWrite<W> {
+    fn new(out: W, compression: Compression) -> std::io::Result<Self> {
+        Ok(match compression {
+            Compression::None => CompressedWrite::Plain(out),
+            #[cfg(feature = "gzip")]
+            Compression::Gz { level } => {
+                CompressedWrite::Gz(flate2::write::GzEncoder::new(out, flate2::Compression::new(level)))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level } => CompressedWrite::Zstd(zstd::Encoder::new(out, level)?),
+        })
+    }
+
+    /// Finalize the encoder, emitting any trailing frame and returning the underlying writer.
+    fn finish(self) -> std::io::Result<W> {
+        match self {
+            CompressedWrite::Plain(mut out) => {
+                out.flush()?;
+                Ok(out)
+            }
+            #[cfg(feature = "gzip")]
+            CompressedWrite::Gz(enc) => enc.finish(),
+            #[cfg(feature = "zstd")]
+            CompressedWrite::Zstd(enc) => enc.finish(),
+        }
+    }
+}
+
+#[cfg(feature = "tar")]
+impl<W: std::io::Write> std::io::Write for CompressedWrite<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressedWrite::Plain(out) => out.write(buf),
+            #[cfg(feature = "gzip")]
+            CompressedWrite::Gz(enc) => enc.write(buf),
+            #[cfg(feature = "zstd")]
+            CompressedWrite::Zstd(enc) => enc.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressedWrite::Plain(out) => out.flush(),
+            #[cfg(feature = "gzip")]
+            CompressedWrite::Gz(enc) => enc.flush(),
+            #[cfg(feature = "zstd")]
+            CompressedWrite::Zstd(enc) => enc.flush(),
+        }
+    }
+}
+
 enum State<W: std::io::Write> {
     Internal(W),
     #[cfg(feature = "tar")]
-    Tar((tar::Builder<W>, Vec<u8>)),
+    Tar((tar::Builder<CompressedWrite<W>>, Vec<u8>)),
+    #[cfg(feature = "zip")]
+    Zip((zip::ZipWriter<std::io::Cursor<Vec<u8>>>, W, Vec<u8>)),
 }
 
 impl<W: std::io::Write> State<W> {
-    pub fn new(format: Format, out: W) -> Self {
+    pub fn new(format: Format, compression: Compression, out: W) -> Self {
         match format {
             Format::InternalTransientNonPersistable => State::Internal(out),
             #[cfg(feature = "tar")]
             Format::Tar => State::Tar((
                 {
-                    let mut ar = tar::Builder::new(out);
+                    let mut ar = tar::Builder::new(
+                        CompressedWrite::new(out, compression).expect("compressor initialization cannot fail"),
+                    );
                     ar.mode(tar::HeaderMode::Deterministic);
                     ar
                 },
                 Vec::with_capacity(64 * 1024),
             )),
+            #[cfg(feature = "zip")]
+            Format::Zip => {
+                let _ = compression;
+                State::Zip((
+                    zip::ZipWriter::new(std::io::Cursor::new(Vec::new())),
+                    out,
+                    Vec::with_capacity(64 * 1024),
+                ))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_entry_round_trips_with_unix_mode() {
+        use gix_object::tree::EntryMode;
+        let mut ar = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        super::write_zip_entry(
+            &mut ar,
+            "bin/tool".into(),
+            EntryMode::BlobExecutable,
+            b"payload",
+            zip::DateTime::default(),
+        )
+        .expect("write entry");
+        let data = ar.finish().expect("finish").into_inner();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).expect("valid archive");
+        let mut file = archive.by_name("bin/tool").expect("entry present");
+        assert_eq!(file.unix_mode(), Some(0o755), "the executable bit is preserved");
+        let mut body = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut body).expect("read body");
+        assert_eq!(body, b"payload");
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_symlink_stores_target_as_body() {
+        use gix_object::tree::EntryMode;
+        let mut ar = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        super::write_zip_entry(&mut ar, "link".into(), EntryMode::Link, b"../target", zip::DateTime::default())
+            .expect("write entry");
+        let data = ar.finish().expect("finish").into_inner();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).expect("valid archive");
+        let mut file = archive.by_name("link").expect("entry present");
+        let mut body = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut body).expect("read body");
+        assert_eq!(body, b"../target", "the symlink target is stored as the file body");
+    }
+
+    #[cfg(all(feature = "tar", feature = "gzip"))]
+    #[test]
+    fn gzip_compressed_write_round_trips() {
+        use std::io::{Read, Write};
+        let mut w = super::CompressedWrite::new(Vec::new(), super::Compression::Gz { level: 6 }).expect("encoder");
+        w.write_all(b"hello gzip").expect("write");
+        let compressed = w.finish().expect("finish");
+
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decoded)
+            .expect("decode");
+        assert_eq!(decoded, "hello gzip");
+    }
+
+    #[cfg(all(feature = "tar", feature = "zstd"))]
+    #[test]
+    fn zstd_compressed_write_round_trips() {
+        use std::io::Write;
+        let mut w = super::CompressedWrite::new(Vec::new(), super::Compression::Zstd { level: 3 }).expect("encoder");
+        w.write_all(b"hello zstd").expect("write");
+        let compressed = w.finish().expect("finish");
+
+        let decoded = zstd::stream::decode_all(compressed.as_slice()).expect("decode");
+        assert_eq!(decoded, b"hello zstd");
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn small_entries_stay_in_memory() {
+        let mut buf = Vec::new();
+        let spooled = super::buffer_or_spool(&mut &b"small"[..], &mut buf, 1024).expect("buffer");
+        assert!(spooled.is_none(), "below the threshold nothing is spooled");
+        assert_eq!(buf, b"small");
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn large_entries_spool_to_disk() {
+        use std::io::Read;
+        let input = vec![b'x'; 4096];
+        let mut buf = Vec::new();
+        let (tmp, size) = super::buffer_or_spool(&mut input.as_slice(), &mut buf, 1024)
+            .expect("buffer")
+            .expect("spooled to disk");
+        assert_eq!(size, input.len() as u64, "the final size is learned from the file length");
+        let mut from_disk = Vec::new();
+        tmp.reopen().expect("reopen").read_to_end(&mut from_disk).expect("read");
+        assert_eq!(from_disk, input, "the whole entry is preserved on disk");
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn threshold_of_zero_never_spools() {
+        let input = vec![b'y'; 4096];
+        let mut buf = Vec::new();
+        let spooled = super::buffer_or_spool(&mut input.as_slice(), &mut buf, 0).expect("buffer");
+        assert!(spooled.is_none(), "a threshold of zero disables spooling");
+        assert_eq!(buf, input, "the entire entry stays in memory");
+    }
+}
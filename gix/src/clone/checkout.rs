@@ -0,0 +1,28 @@
+/// Modification
+pub mod main_worktree {
+    use std::path::PathBuf;
+
+    /// The error returned by [`PrepareCheckout::main_worktree()`][crate::clone::PrepareCheckout::main_worktree()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("Cannot checkout a working tree for a bare repository at \"{}\"", git_dir.display())]
+        BareRepository { git_dir: PathBuf },
+        #[error(transparent)]
+        FindHead(#[from] crate::reference::find::existing::Error),
+        #[error(transparent)]
+        PeelHeadToId(#[from] crate::reference::peel::Error),
+        #[error(transparent)]
+        FindObject(#[from] crate::object::find::existing::Error),
+        #[error(transparent)]
+        PeelToTree(#[from] crate::object::peel::to_kind::Error),
+        #[error(transparent)]
+        IndexFromTree(#[from] crate::repository::index_from_tree::Error),
+        #[error(transparent)]
+        WriteIndex(#[from] gix_index::file::write::Error),
+        #[error(transparent)]
+        CheckoutOptions(#[from] crate::config::checkout_options::Error),
+        #[error(transparent)]
+        CheckOut(#[from] gix_worktree::index::checkout::Error<crate::odb::find::existing_object::Error>),
+    }
+}
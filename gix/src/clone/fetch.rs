@@ -0,0 +1,78 @@
+use std::sync::atomic::AtomicBool;
+
+use crate::{clone::PrepareFetch, Progress};
+
+/// The error returned by [`PrepareFetch::fetch_only()`].
+#[derive(Debug, thiserror::Error)]
+#[allow(missing_docs)]
+#[cfg(feature = "blocking-network-client")]
+pub enum Error {
+    #[error(transparent)]
+    Connect(#[from] crate::remote::connect::Error),
+    #[error(transparent)]
+    PrepareFetch(#[from] crate::remote::fetch::prepare::Error),
+    #[error(transparent)]
+    Fetch(#[from] crate::remote::fetch::Error),
+    #[error(transparent)]
+    RemoteInit(#[from] crate::remote::init::Error),
+    #[error("Custom configuration of the connection failed")]
+    RemoteConnection(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error(transparent)]
+    RemoteConfiguration(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Modification
+impl PrepareFetch {
+    /// Fetch a pack and update local branches according to refspecs, providing `progress` and checking `should_interrupt` to
+    /// abort operations. Return the newly fetched repository and the fetch outcome.
+    ///
+    /// The `shallow` configuration is honored so that only the requested slice of history along with the `shallow` file are
+    /// written, and [`configure_connection()`][Self::configure_connection()] is invoked right before the fetch if set.
+    #[cfg(feature = "blocking-network-client")]
+    pub fn fetch_only<P>(
+        &mut self,
+        mut progress: P,
+        should_interrupt: &AtomicBool,
+    ) -> Result<(crate::Repository, crate::remote::fetch::Outcome), Error>
+    where
+        P: Progress,
+        P::SubProgress: 'static,
+    {
+        let repo = self
+            .repo
+            .as_mut()
+            .expect("user error: multiple calls are allowed only until it succeeds");
+
+        let mut remote = repo.remote_at(self.url.clone())?;
+        if let Some(f) = self.configure_remote.as_mut() {
+            remote = f(remote).map_err(Error::RemoteConfiguration)?;
+        }
+
+        let mut connection = remote.connect(crate::remote::Direction::Fetch)?;
+        if let Some(f) = self.configure_connection.as_mut() {
+            f(&mut connection).map_err(Error::RemoteConnection)?;
+        }
+        let outcome = connection
+            .prepare_fetch(&mut progress, self.fetch_options.clone())?
+            .with_shallow(self.shallow.clone())
+            .receive(&mut progress, should_interrupt)?;
+
+        let repo = self.repo.take().expect("still present as it was just mutated");
+        Ok((repo, outcome))
+    }
+
+    /// Similar to [`fetch_only()`][Self::fetch_only()], but passes ownership to a utility type to configure a checkout operation.
+    #[cfg(feature = "blocking-network-client")]
+    pub fn fetch_then_checkout<P>(
+        &mut self,
+        progress: P,
+        should_interrupt: &AtomicBool,
+    ) -> Result<(crate::clone::PrepareCheckout, crate::remote::fetch::Outcome), Error>
+    where
+        P: Progress,
+        P::SubProgress: 'static,
+    {
+        let (repo, outcome) = self.fetch_only(progress, should_interrupt)?;
+        Ok((crate::clone::PrepareCheckout { repo: Some(repo) }, outcome))
+    }
+}
@@ -6,6 +6,12 @@ use crate::config::tree::gitoxide;
 
 type ConfigureRemoteFn =
     Box<dyn FnMut(crate::Remote<'_>) -> Result<crate::Remote<'_>, Box<dyn std::error::Error + Send + Sync>>>;
+#[cfg(feature = "blocking-network-client")]
+type ConfigureConnectionFn = Box<
+    dyn FnMut(
+        &mut crate::remote::Connection<'_, '_, Box<dyn gix_protocol::transport::client::Transport + Send>>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+>;
 
 /// A utility to collect configuration on how to fetch from a remote and initiate a fetch operation. It will delete the newly
 /// created repository on when dropped without successfully finishing a fetch.
@@ -17,6 +23,12 @@ pub struct PrepareFetch {
     remote_name: Option<BString>,
     /// A function to configure a remote prior to fetching a pack.
     configure_remote: Option<ConfigureRemoteFn>,
+    /// A function to configure the connection right before the actual fetch.
+    #[cfg(feature = "blocking-network-client")]
+    configure_connection: Option<ConfigureConnectionFn>,
+    /// How to limit the amount of history to fetch, defaulting to all of it.
+    #[cfg(any(feature = "async-network-client", feature = "blocking-network-client"))]
+    shallow: crate::remote::fetch::Shallow,
     /// Options for preparing a fetch operation.
     #[cfg(any(feature = "async-network-client", feature = "blocking-network-client"))]
     fetch_options: crate::remote::ref_map::Options,
@@ -100,10 +112,45 @@ impl PrepareFetch {
             repo: Some(repo),
             remote_name: None,
             configure_remote: None,
+            #[cfg(feature = "blocking-network-client")]
+            configure_connection: None,
+            #[cfg(any(feature = "async-network-client", feature = "blocking-network-client"))]
+            shallow: crate::remote::fetch::Shallow::default(),
         })
     }
 }
 
+/// Builder
+impl PrepareFetch {
+    /// Limit the amount of history to fetch, e.g. to a certain `--depth`, a cutoff date or a set of excluded refs,
+    /// with the default being to fetch all of it.
+    ///
+    /// Note that only the requested slice of history along with the `shallow` file will be written, just like `git`
+    /// would do it for a shallow clone.
+    #[cfg(any(feature = "async-network-client", feature = "blocking-network-client"))]
+    pub fn with_shallow(mut self, shallow: crate::remote::fetch::Shallow) -> Self {
+        self.shallow = shallow;
+        self
+    }
+
+    /// Set a function to configure the actual connection to use when fetching, i.e. to adjust timeouts, headers or other
+    /// transport-specific options right before the pack is negotiated.
+    ///
+    /// It is invoked after the remote was connected and right before the fetch is performed, mirroring
+    /// `configure_remote()` which adjusts the remote itself.
+    #[cfg(feature = "blocking-network-client")]
+    pub fn configure_connection(
+        mut self,
+        f: impl FnMut(
+                &mut crate::remote::Connection<'_, '_, Box<dyn gix_protocol::transport::client::Transport + Send>>,
+            ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+            + 'static,
+    ) -> Self {
+        self.configure_connection = Some(Box::new(f));
+        self
+    }
+}
+
 /// A utility to collect configuration on how to perform a checkout into a working tree, and when dropped without checking out successfully
 /// the fetched repository will be dropped.
 #[must_use]
@@ -112,6 +159,66 @@ pub struct PrepareCheckout {
     pub(self) repo: Option<crate::Repository>,
 }
 
+/// Modification
+impl PrepareCheckout {
+    /// Checkout the main worktree, determining the tree to check out from the current `HEAD` and reporting
+    /// progress along the way, with `should_interrupt` allowing to abort the lengthy operation.
+    ///
+    /// Note that this will fail on [bare repositories][crate::Repository::is_bare()], and be a no-op leaving
+    /// an unborn `HEAD` in place if the remote didn't have anything to offer.
+    #[cfg(feature = "blocking-network-client")]
+    pub fn main_worktree<P>(
+        &mut self,
+        mut progress: P,
+        should_interrupt: &std::sync::atomic::AtomicBool,
+    ) -> Result<(crate::Repository, gix_worktree::index::checkout::Outcome), checkout::main_worktree::Error>
+    where
+        P: crate::Progress,
+    {
+        use checkout::main_worktree::Error;
+        let repo = self
+            .repo
+            .take()
+            .expect("BUG: this method may only be called until it is successful");
+        let workdir = repo.work_dir().ok_or_else(|| Error::BareRepository {
+            git_dir: repo.git_dir().to_owned(),
+        })?;
+
+        let root_tree = match repo.head()?.peel_to_id_in_place().transpose()? {
+            Some(id) => id.object()?.peel_to_kind(gix_object::Kind::Tree)?.id,
+            None => {
+                // Unborn `HEAD`: the remote was empty, so there is nothing to check out.
+                return Ok((repo, gix_worktree::index::checkout::Outcome::default()));
+            }
+        };
+
+        let index = repo.index_from_tree(&root_tree)?;
+        let mut index = gix_index::File::from_state(index, repo.git_dir().join("index"));
+
+        let mut opts = repo.config.checkout_options(repo.git_dir())?;
+        opts.destination_is_initially_empty = true;
+
+        let mut files = progress.add_child("checkout");
+        let mut bytes = progress.add_child("writing");
+
+        let outcome = gix_worktree::index::checkout(
+            &mut index,
+            workdir,
+            {
+                let objects = repo.objects.clone().into_arc().expect("thread-safe odb after clone");
+                move |oid, buf| objects.find_blob(oid, buf)
+            },
+            &mut files,
+            &mut bytes,
+            should_interrupt,
+            opts,
+        )?;
+        index.write(gix_index::write::Options::default())?;
+
+        Ok((repo, outcome))
+    }
+}
+
 ///
 pub mod fetch;
 